@@ -1,9 +1,21 @@
+use bevy::core_pipeline::bloom::BloomSettings;
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
 use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
 use bevy::window::PrimaryWindow;
 use rand::prelude::random;
+use std::time::Duration;
+
+const SHOW_FPS_OVERLAY: bool = true;
 
 const SNAKE_HEAD_COLOR: Color = Color::rgb(0.7, 0.7, 0.7);
+const SNAKE_SEGMENT_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
 const FOOD_COLOR: Color = Color::rgb(1.0, 0.0, 1.0);
+
+const SNAKE_HEAD_COLOR_NEON: Color = Color::rgb(2.5, 2.5, 2.5);
+const SNAKE_SEGMENT_COLOR_NEON: Color = Color::rgb(1.3, 1.3, 1.3);
+const FOOD_COLOR_NEON: Color = Color::rgb(3.0, 0.0, 3.0);
 const ARENA_WIDTH: u32 = 10;
 const ARENA_HEIGHT: u32 = 10;
 const RES_HEIGHT: f32 = 500.;
@@ -35,9 +47,58 @@ struct SnakeHead {
     direction: Direction,
 }
 
+#[derive(Component)]
+struct SnakeSegment;
+
+#[derive(Resource, Default)]
+struct SnakeSegments(Vec<Entity>);
+
+#[derive(Resource, Default)]
+struct LastTailPosition(Option<Position>);
+
+#[derive(Resource)]
+struct ArenaRules {
+    wrap: bool,
+}
+
+impl Default for ArenaRules {
+    fn default() -> Self {
+        Self { wrap: true }
+    }
+}
+
+#[derive(Resource, Default)]
+struct Score(u32);
+
+#[derive(Component)]
+struct ScoreText;
+
+#[derive(Component)]
+struct FpsText;
+
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq)]
+enum RenderStyle {
+    #[default]
+    Flat,
+    Neon,
+}
+
+impl RenderStyle {
+    fn resolve(&self, flat: Color, neon: Color) -> Color {
+        match self {
+            Self::Flat => flat,
+            Self::Neon => neon,
+        }
+    }
+}
+
 #[derive(Component)]
 struct Food;
 
+struct GrowthEvent;
+
+struct GameOverEvent;
+
 #[derive(PartialEq, Copy, Clone)]
 enum Direction {
     Left,
@@ -57,27 +118,71 @@ impl Direction {
     }
 }
 
-#[derive(Component, Deref, DerefMut)]
-pub struct FoodSpawnTimer(Timer);
+fn food_spawner(mut commands: Commands, render_style: Res<RenderStyle>) {
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: render_style.resolve(FOOD_COLOR, FOOD_COLOR_NEON),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Food)
+        .insert(Position {
+            x: (random::<f32>() * ARENA_WIDTH as f32) as i32,
+            y: (random::<f32>() * ARENA_HEIGHT as f32) as i32,
+        })
+        .insert(Size::square(0.8));
+}
 
-fn food_spawner(time: Res<Time>, mut query: Query<&mut FoodSpawnTimer>, mut commands: Commands) {
-    let mut timer = query.get_single_mut().unwrap();
+fn snake_eating(
+    mut commands: Commands,
+    mut score: ResMut<Score>,
+    mut growth_writer: EventWriter<GrowthEvent>,
+    food_positions: Query<(Entity, &Position), With<Food>>,
+    head_positions: Query<&Position, With<SnakeHead>>,
+) {
+    for head_pos in head_positions.iter() {
+        for (food_entity, food_pos) in food_positions.iter() {
+            if food_pos == head_pos {
+                commands.entity(food_entity).despawn();
+                growth_writer.send(GrowthEvent);
+                score.0 += 1;
+            }
+        }
+    }
+}
 
-    if timer.tick(time.delta()).just_finished() {
-        commands
-            .spawn(SpriteBundle {
-                sprite: Sprite {
-                    color: FOOD_COLOR,
-                    ..default()
-                },
-                ..default()
-            })
-            .insert(Food)
-            .insert(Position {
-                x: ((random::<f32>() - 0.5) * ARENA_WIDTH as f32) as i32,
-                y: ((random::<f32>() - 0.5) * ARENA_HEIGHT as f32) as i32,
-            })
-            .insert(Size::square(0.8));
+fn snake_growth(
+    mut commands: Commands,
+    last_tail_position: Res<LastTailPosition>,
+    mut segments: ResMut<SnakeSegments>,
+    mut growth_reader: EventReader<GrowthEvent>,
+    render_style: Res<RenderStyle>,
+) {
+    if growth_reader.iter().next().is_some() {
+        segments.0.push(spawn_segment(
+            &mut commands,
+            last_tail_position.0.unwrap(),
+            &render_style,
+        ));
+    }
+}
+
+fn game_over(
+    mut commands: Commands,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    segments_res: ResMut<SnakeSegments>,
+    mut score: ResMut<Score>,
+    render_style: Res<RenderStyle>,
+    despawnable: Query<Entity, Or<(With<Food>, With<SnakeSegment>)>>,
+) {
+    if game_over_reader.iter().next().is_some() {
+        for ent in despawnable.iter() {
+            commands.entity(ent).despawn();
+        }
+        score.0 = 0;
+        spawn_snake(commands, segments_res, render_style);
     }
 }
 
@@ -85,11 +190,112 @@ fn setup_camera(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
 }
 
-fn spawn_snake(mut commands: Commands) {
-    commands
+fn toggle_render_style(keyboard_input: Res<Input<KeyCode>>, mut render_style: ResMut<RenderStyle>) {
+    if keyboard_input.just_pressed(KeyCode::Backquote) {
+        *render_style = match *render_style {
+            RenderStyle::Flat => RenderStyle::Neon,
+            RenderStyle::Neon => RenderStyle::Flat,
+        };
+    }
+}
+
+fn sync_render_style(
+    mut commands: Commands,
+    render_style: Res<RenderStyle>,
+    mut cameras: Query<(Entity, &mut Camera, &mut Tonemapping, Option<&BloomSettings>), With<Camera2d>>,
+) {
+    if !render_style.is_changed() {
+        return;
+    }
+    for (entity, mut camera, mut tonemapping, bloom) in cameras.iter_mut() {
+        match *render_style {
+            RenderStyle::Neon => {
+                camera.hdr = true;
+                *tonemapping = Tonemapping::TonyMcMapface;
+                if bloom.is_none() {
+                    commands.entity(entity).insert(BloomSettings::default());
+                }
+            }
+            RenderStyle::Flat => {
+                camera.hdr = false;
+                *tonemapping = Tonemapping::default();
+                if bloom.is_some() {
+                    commands.entity(entity).remove::<BloomSettings>();
+                }
+            }
+        }
+    }
+}
+
+fn setup_ui(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "Score: 0",
+            TextStyle {
+                font_size: 32.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            left: Val::Px(8.0),
+            ..default()
+        }),
+        ScoreText,
+    ));
+}
+
+fn update_score_text(score: Res<Score>, mut text: Query<&mut Text, With<ScoreText>>) {
+    if let Ok(mut text) = text.get_single_mut() {
+        text.sections[0].value = format!("Score: {}", score.0);
+    }
+}
+
+fn setup_fps_overlay(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "FPS: --",
+            TextStyle {
+                font_size: 20.0,
+                color: Color::YELLOW,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            right: Val::Px(8.0),
+            ..default()
+        }),
+        FpsText,
+    ));
+}
+
+fn update_fps_overlay(
+    diagnostics: Res<DiagnosticsStore>,
+    mut text: Query<&mut Text, With<FpsText>>,
+) {
+    if let Ok(mut text) = text.get_single_mut() {
+        if let Some(fps) = diagnostics
+            .get(&FrameTimeDiagnosticsPlugin::FPS)
+            .and_then(|fps| fps.smoothed())
+        {
+            text.sections[0].value = format!("FPS: {fps:.0}");
+        }
+    }
+}
+
+fn spawn_snake(
+    mut commands: Commands,
+    mut segments: ResMut<SnakeSegments>,
+    render_style: Res<RenderStyle>,
+) {
+    let head = commands
         .spawn(SpriteBundle {
             sprite: Sprite {
-                color: SNAKE_HEAD_COLOR,
+                color: render_style.resolve(SNAKE_HEAD_COLOR, SNAKE_HEAD_COLOR_NEON),
                 ..default()
             },
             ..default()
@@ -97,25 +303,30 @@ fn spawn_snake(mut commands: Commands) {
         .insert(SnakeHead {
             direction: Direction::Up,
         })
+        .insert(SnakeSegment)
         .insert(Position { x: 3, y: 3 })
-        .insert(Size::square(0.8));
-}
+        .insert(Size::square(0.8))
+        .id();
 
-fn spawn_food_timer(mut commands: Commands) {
-    commands.spawn(FoodSpawnTimer(Timer::from_seconds(
-        1.,
-        TimerMode::Repeating,
-    )));
+    *segments = SnakeSegments(vec![
+        head,
+        spawn_segment(&mut commands, Position { x: 3, y: 2 }, &render_style),
+    ]);
 }
 
-#[derive(Component, Deref, DerefMut)]
-struct MovementTimer(Timer);
-
-fn spawn_movement_timer(mut commands: Commands) {
-    commands.spawn(MovementTimer(Timer::from_seconds(
-        0.15,
-        TimerMode::Repeating,
-    )));
+fn spawn_segment(commands: &mut Commands, position: Position, render_style: &RenderStyle) -> Entity {
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: render_style.resolve(SNAKE_SEGMENT_COLOR, SNAKE_SEGMENT_COLOR_NEON),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(SnakeSegment)
+        .insert(position)
+        .insert(Size::square(0.65))
+        .id()
 }
 
 fn size_scaling(
@@ -154,15 +365,21 @@ fn position_translation(
     }
 }
 
+fn toggle_arena_rules(keyboard_input: Res<Input<KeyCode>>, mut arena_rules: ResMut<ArenaRules>) {
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        arena_rules.wrap = !arena_rules.wrap;
+    }
+}
+
 fn snake_movement_input(keyboard_input: Res<Input<KeyCode>>, mut heads: Query<&mut SnakeHead>) {
     if let Some(mut head) = heads.iter_mut().next() {
-        let dir: Direction = if keyboard_input.pressed(KeyCode::Left) {
+        let dir: Direction = if keyboard_input.pressed(KeyCode::ArrowLeft) {
             Direction::Left
-        } else if keyboard_input.pressed(KeyCode::Down) {
+        } else if keyboard_input.pressed(KeyCode::ArrowDown) {
             Direction::Down
-        } else if keyboard_input.pressed(KeyCode::Up) {
+        } else if keyboard_input.pressed(KeyCode::ArrowUp) {
             Direction::Up
-        } else if keyboard_input.pressed(KeyCode::Right) {
+        } else if keyboard_input.pressed(KeyCode::ArrowRight) {
             Direction::Right
         } else {
             head.direction
@@ -174,38 +391,90 @@ fn snake_movement_input(keyboard_input: Res<Input<KeyCode>>, mut heads: Query<&m
 }
 
 fn snake_movement(
-    time: Res<Time>,
-    mut timer: Query<&mut MovementTimer>,
-    mut heads: Query<(&mut Position, &SnakeHead)>,
+    arena_rules: Res<ArenaRules>,
+    segments: ResMut<SnakeSegments>,
+    mut heads: Query<(Entity, &SnakeHead)>,
+    mut positions: Query<&mut Position>,
+    mut last_tail_position: ResMut<LastTailPosition>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
 ) {
-    let mut timer = timer.get_single_mut().unwrap();
-    if timer.tick(time.delta()).just_finished() {
-        if let Some((mut head_pos, head)) = heads.iter_mut().next() {
-            match &head.direction {
-                Direction::Left => head_pos.x = (head_pos.x - 1) % ARENA_WIDTH as i32,
-                Direction::Right => head_pos.x = (head_pos.x + 1) % ARENA_WIDTH as i32,
-                Direction::Up => {
-                    head_pos.y = (head_pos.y + 1) % ARENA_HEIGHT as i32;
-                }
-                Direction::Down => {
-                    head_pos.y = (head_pos.y - 1) % ARENA_HEIGHT as i32;
-                }
-            }
+    if let Some((head_entity, head)) = heads.iter_mut().next() {
+        let segment_positions = segments
+            .0
+            .iter()
+            .map(|e| *positions.get_mut(*e).unwrap())
+            .collect::<Vec<Position>>();
+        let mut head_pos = positions.get_mut(head_entity).unwrap();
+        let mut new_pos = *head_pos;
+        match &head.direction {
+            Direction::Left => new_pos.x -= 1,
+            Direction::Right => new_pos.x += 1,
+            Direction::Up => new_pos.y += 1,
+            Direction::Down => new_pos.y -= 1,
+        }
+
+        if arena_rules.wrap {
+            new_pos.x = new_pos.x.rem_euclid(ARENA_WIDTH as i32);
+            new_pos.y = new_pos.y.rem_euclid(ARENA_HEIGHT as i32);
+        } else if new_pos.x < 0
+            || new_pos.x >= ARENA_WIDTH as i32
+            || new_pos.y < 0
+            || new_pos.y >= ARENA_HEIGHT as i32
+        {
+            game_over_writer.send(GameOverEvent);
+            return;
         }
+        *head_pos = new_pos;
+
+        if segment_positions.contains(&*head_pos) {
+            game_over_writer.send(GameOverEvent);
+        }
+        segment_positions
+            .iter()
+            .zip(segments.0.iter().skip(1))
+            .for_each(|(pos, segment)| {
+                *positions.get_mut(*segment).unwrap() = *pos;
+            });
+        *last_tail_position = LastTailPosition(Some(*segment_positions.last().unwrap()));
     }
 }
 
 fn main() {
-    App::new()
-        .add_startup_system(setup_camera)
-        .add_startup_system(spawn_snake)
-        .add_startup_system(spawn_food_timer)
-        .add_startup_system(spawn_movement_timer)
-        .add_system(snake_movement)
-        .add_system(food_spawner)
-        .add_system(snake_movement_input.before(snake_movement))
-        .add_systems((position_translation, size_scaling).in_base_set(CoreSet::PostUpdate))
+    let mut app = App::new();
+    app.add_systems(Startup, (setup_camera, setup_ui, spawn_snake))
+        .insert_resource(Time::<Fixed>::from_seconds(0.15))
+        .add_systems(
+            FixedUpdate,
+            (
+                snake_movement,
+                snake_eating.after(snake_movement),
+                snake_growth.after(snake_eating),
+                game_over.after(snake_movement),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                // Bevy only exposes one `Time<Fixed>` clock, so food spawning can't get
+                // its own independent fixed-timestep period; `on_timer` approximates the
+                // ~1s cadence off the real-time `Update` clock instead.
+                food_spawner.run_if(on_timer(Duration::from_secs_f32(1.))),
+                snake_movement_input,
+                toggle_arena_rules,
+                toggle_render_style,
+                sync_render_style.after(toggle_render_style),
+                update_score_text,
+            ),
+        )
+        .add_systems(PostUpdate, (position_translation, size_scaling))
         .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
+        .init_resource::<SnakeSegments>()
+        .init_resource::<LastTailPosition>()
+        .init_resource::<ArenaRules>()
+        .init_resource::<Score>()
+        .init_resource::<RenderStyle>()
+        .add_event::<GrowthEvent>()
+        .add_event::<GameOverEvent>()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Snake!".to_string(),
@@ -213,6 +482,16 @@ fn main() {
                 ..default()
             }),
             ..default()
-        }))
-        .run();
+        }));
+
+    if SHOW_FPS_OVERLAY {
+        // Bevy's built-in FpsOverlayPlugin needs a newer Bevy than the rest of this
+        // crate targets, so this is a hand-rolled stand-in (FrameTimeDiagnosticsPlugin
+        // plus a plain Text HUD) rather than the plugin itself.
+        app.add_plugins(FrameTimeDiagnosticsPlugin::default())
+            .add_systems(Startup, setup_fps_overlay)
+            .add_systems(Update, update_fps_overlay);
+    }
+
+    app.run();
 }